@@ -0,0 +1,117 @@
+use std::iter::Peekable;
+
+/// Merges two sorted, deduplicated iterators of thread ids into their union,
+/// still sorted and deduplicated.
+pub struct MergeOr<'a, L: Iterator<Item = &'a usize>, R: Iterator<Item = &'a usize>> {
+    left: Peekable<L>,
+    right: Peekable<R>,
+}
+
+impl<'a, L: Iterator<Item = &'a usize>, R: Iterator<Item = &'a usize>> MergeOr<'a, L, R> {
+    pub fn new(left: L, right: R) -> Self {
+        Self {
+            left: left.peekable(),
+            right: right.peekable(),
+        }
+    }
+}
+
+impl<'a, L: Iterator<Item = &'a usize>, R: Iterator<Item = &'a usize>> Iterator
+    for MergeOr<'a, L, R>
+{
+    type Item = &'a usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match (self.left.peek(), self.right.peek()) {
+            (Some(&l), Some(&r)) => {
+                if l < r {
+                    self.left.next()
+                } else if l > r {
+                    self.right.next()
+                } else {
+                    self.right.next();
+                    self.left.next()
+                }
+            }
+            (Some(_), None) => self.left.next(),
+            (None, Some(_)) => self.right.next(),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Merges two sorted iterators of thread ids into their intersection.
+pub struct MergeAnd<'a, L: Iterator<Item = &'a usize>, R: Iterator<Item = &'a usize>> {
+    left: Peekable<L>,
+    right: Peekable<R>,
+}
+
+impl<'a, L: Iterator<Item = &'a usize>, R: Iterator<Item = &'a usize>> MergeAnd<'a, L, R> {
+    pub fn new(left: L, right: R) -> Self {
+        Self {
+            left: left.peekable(),
+            right: right.peekable(),
+        }
+    }
+}
+
+impl<'a, L: Iterator<Item = &'a usize>, R: Iterator<Item = &'a usize>> Iterator
+    for MergeAnd<'a, L, R>
+{
+    type Item = &'a usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match (self.left.peek(), self.right.peek()) {
+                (Some(&l), Some(&r)) => {
+                    if l < r {
+                        self.left.next();
+                    } else if l > r {
+                        self.right.next();
+                    } else {
+                        self.right.next();
+                        return self.left.next();
+                    }
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Sorted set-difference: ids present in `left` but absent from `right`.
+pub struct MergeNot<'a, L: Iterator<Item = &'a usize>, R: Iterator<Item = &'a usize>> {
+    left: Peekable<L>,
+    right: Peekable<R>,
+}
+
+impl<'a, L: Iterator<Item = &'a usize>, R: Iterator<Item = &'a usize>> MergeNot<'a, L, R> {
+    pub fn new(left: L, right: R) -> Self {
+        Self {
+            left: left.peekable(),
+            right: right.peekable(),
+        }
+    }
+}
+
+impl<'a, L: Iterator<Item = &'a usize>, R: Iterator<Item = &'a usize>> Iterator
+    for MergeNot<'a, L, R>
+{
+    type Item = &'a usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let l = *self.left.peek()?;
+            match self.right.peek() {
+                Some(&r) if r < l => {
+                    self.right.next();
+                }
+                Some(&r) if r == l => {
+                    self.right.next();
+                    self.left.next();
+                }
+                _ => return self.left.next(),
+            }
+        }
+    }
+}
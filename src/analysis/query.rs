@@ -0,0 +1,187 @@
+use anyhow::{bail, Result};
+use std::iter::Peekable;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchQuery {
+    Word(String),
+    /// A term that should match every vocabulary key it is a prefix of,
+    /// either written explicitly as `word*` or implied for the last word of
+    /// the query so as-you-type search surfaces hits before the word is finished.
+    Prefix(String),
+    /// A quoted `"exact phrase"`: matches only threads where every word
+    /// occurs, in this order, at consecutive positions inside one message.
+    Phrase(Vec<String>),
+    And((Box<SearchQuery>, Box<SearchQuery>)),
+    Or((Box<SearchQuery>, Box<SearchQuery>)),
+    /// `A and not B`, written `A -B` or `A NOT B`.
+    Not((Box<SearchQuery>, Box<SearchQuery>)),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+pub struct Lexer<'a> {
+    chars: Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+        }
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        while matches!(self.chars.peek(), Some(c) if c.is_whitespace()) {
+            self.chars.next();
+        }
+        match self.chars.peek()? {
+            '(' => {
+                self.chars.next();
+                Some(Token::LParen)
+            }
+            ')' => {
+                self.chars.next();
+                Some(Token::RParen)
+            }
+            '-' => {
+                self.chars.next();
+                Some(Token::Not)
+            }
+            '"' => {
+                self.chars.next();
+                let mut phrase = String::new();
+                for c in self.chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    phrase.push(c);
+                }
+                Some(Token::Phrase(
+                    phrase.split_whitespace().map(str::to_string).collect(),
+                ))
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    word.push(c);
+                    self.chars.next();
+                }
+                match word.as_str() {
+                    "AND" => Some(Token::And),
+                    "OR" => Some(Token::Or),
+                    "NOT" => Some(Token::Not),
+                    _ => match word.strip_suffix('*') {
+                        Some(stripped) => Some(Token::Prefix(stripped.to_string())),
+                        None => Some(Token::Word(word)),
+                    },
+                }
+            }
+        }
+    }
+}
+
+pub struct Parser<T: Iterator<Item = Token>> {
+    tokens: Peekable<T>,
+}
+
+impl<T: Iterator<Item = Token>> Parser<T> {
+    pub fn new(lexer: T) -> Result<Self> {
+        Ok(Self {
+            tokens: lexer.peekable(),
+        })
+    }
+
+    pub fn parse(mut self) -> Result<SearchQuery> {
+        let mut query = self.parse_or()?;
+        if self.tokens.peek().is_some() {
+            bail!("unexpected trailing tokens in query");
+        }
+        mark_trailing_word_as_prefix(&mut query);
+        Ok(query)
+    }
+
+    fn parse_or(&mut self) -> Result<SearchQuery> {
+        let mut query = self.parse_and()?;
+        while matches!(self.tokens.peek(), Some(Token::Or)) {
+            self.tokens.next();
+            let rhs = self.parse_and()?;
+            query = SearchQuery::Or((Box::new(query), Box::new(rhs)));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<SearchQuery> {
+        let mut query = self.parse_term()?;
+        loop {
+            let excluding = match self.tokens.peek() {
+                Some(Token::And) => {
+                    self.tokens.next();
+                    false
+                }
+                Some(Token::Not) => {
+                    self.tokens.next();
+                    true
+                }
+                Some(Token::Word(_)) | Some(Token::Prefix(_)) | Some(Token::Phrase(_))
+                | Some(Token::LParen) => false,
+                _ => break,
+            };
+            let rhs = self.parse_term()?;
+            query = if excluding {
+                SearchQuery::Not((Box::new(query), Box::new(rhs)))
+            } else {
+                SearchQuery::And((Box::new(query), Box::new(rhs)))
+            };
+        }
+        Ok(query)
+    }
+
+    fn parse_term(&mut self) -> Result<SearchQuery> {
+        match self.tokens.next() {
+            Some(Token::Word(word)) => Ok(SearchQuery::Word(word)),
+            Some(Token::Prefix(word)) => Ok(SearchQuery::Prefix(word)),
+            Some(Token::Phrase(words)) => Ok(SearchQuery::Phrase(words)),
+            Some(Token::LParen) => {
+                let query = self.parse_or()?;
+                match self.tokens.next() {
+                    Some(Token::RParen) => Ok(query),
+                    _ => bail!("expected closing parenthesis"),
+                }
+            }
+            other => bail!("expected a word or '(', found {other:?}"),
+        }
+    }
+}
+
+/// By default the last word of a query is treated as a prefix term, so
+/// incremental/as-you-type search surfaces hits before the user finishes
+/// typing it. A word already marked explicit (`word*`) is left alone.
+fn mark_trailing_word_as_prefix(query: &mut SearchQuery) {
+    match query {
+        SearchQuery::Word(word) => *query = SearchQuery::Prefix(std::mem::take(word)),
+        SearchQuery::Prefix(_) | SearchQuery::Phrase(_) => {}
+        SearchQuery::And((_, right)) | SearchQuery::Or((_, right)) => {
+            mark_trailing_word_as_prefix(right)
+        }
+        // The excluded side of a `Not` isn't the trailing word the user is
+        // still typing, so it stays an exact lemma.
+        SearchQuery::Not(_) => {}
+    }
+}
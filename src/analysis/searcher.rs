@@ -1,12 +1,33 @@
 use crate::analysis::deserialization::{deserialize_messages, Message, TextEntity};
 use crate::analysis::lemmatizer::Lemmatizer;
-use crate::analysis::merge::{MergeAnd, MergeOr};
+use crate::analysis::merge::{MergeAnd, MergeNot, MergeOr};
 use crate::analysis::query::{Lexer, Parser, SearchQuery};
 use crate::analysis::thread_dsu::ThreadDSU;
 use crate::analysis::utils;
+use fst::{IntoStreamer, Set, Streamer};
+use levenshtein_automata::{LevenshteinAutomatonBuilder, DFA};
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
+/// Maximum number of vocabulary terms a single typo-tolerant lookup is allowed
+/// to expand into, so a very permissive edit distance can't blow up a query.
+const MAX_TYPO_EXPANSION: usize = 32;
+
+/// Same edit-distance budgets MeiliSearch uses: the shorter the word, the
+/// less room there is for a typo before the automaton starts matching noise.
+fn typo_budget(word: &str) -> u8 {
+    match word.chars().count() {
+        0..=4 => 0,
+        5..=8 => 1,
+        _ => 2,
+    }
+}
+
+/// Whether `phrase` occurs as a consecutive run inside `tokens`.
+fn contains_consecutive(tokens: &[String], phrase: &[String]) -> bool {
+    !phrase.is_empty() && phrase.len() <= tokens.len() && tokens.windows(phrase.len()).any(|window| window == phrase)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Text {
     Plain(String),
@@ -33,10 +54,81 @@ pub struct Searcher {
     threads: Vec<Vec<usize>>,
     lemmatizer: Arc<Mutex<Lemmatizer>>,
     thread_index: HashMap<String, Vec<usize>>,
+    /// Sorted finite-state transducer over `thread_index`'s keys, built once
+    /// so typo-tolerant lookups can stream a Levenshtein automaton against
+    /// the vocabulary instead of scanning every key.
+    word_fst: Set<Vec<u8>>,
+    /// Per-message ordered lemma tokens (same `len() > 3` filter as
+    /// `thread_index`), used to build BM25 term frequencies.
+    message_tokens: HashMap<usize, Vec<String>>,
+    /// Per-message ordered lemma tokens with *no* length filter, used to
+    /// verify phrase queries match a consecutive run inside a single
+    /// message. Phrases routinely contain short words ("new york", "the cat
+    /// sat") that `message_tokens`/`thread_index` drop, so phrase matching
+    /// needs its own unfiltered token stream.
+    message_phrase_tokens: HashMap<usize, Vec<String>>,
+    /// Per-thread lemma term frequencies, used for BM25 relevance scoring.
+    thread_term_freq: Vec<HashMap<String, usize>>,
+    /// Per-thread token count (BM25's `dl`).
+    thread_token_count: Vec<usize>,
+    /// Mean token count across all threads (BM25's `avgdl`).
+    avgdl: f64,
+    /// Lemma -> equivalent lemmas, consulted at query time so a `Word` term
+    /// is also matched against its synonyms.
+    synonyms: HashMap<String, Vec<String>>,
+    /// One embedding vector per thread (same indices as `threads`), set via
+    /// `set_thread_embeddings` once a caller has embedded `thread_texts`.
+    /// `None` until then, in which case search stays lexical-only.
+    thread_embeddings: Option<Vec<Vec<f32>>>,
+}
+
+/// Knobs for [`Searcher::find_threads`]. Defaults reproduce the lexical-only,
+/// date-sorted behavior: no vector query, no score floors, no truncation.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    pub sort_mode: SortMode,
+    pub min_score_text: f32,
+    pub min_score_vector: f32,
+    pub top_k: Option<usize>,
+    pub query_embedding: Option<Vec<f32>>,
+}
+
+impl Default for SortMode {
+    fn default() -> Self {
+        SortMode::Date
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Term-frequency saturation knob: higher values let repeated occurrences of
+/// a term keep adding to the score for longer before it plateaus.
+const BM25_K1: f64 = 1.2;
+/// Length-normalization strength: 0 ignores document length entirely, 1
+/// fully normalizes by it.
+const BM25_B: f64 = 0.75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortMode {
+    Relevance,
+    Date,
 }
 
 impl Searcher {
-    pub fn new(lemmatizer: Arc<Mutex<Lemmatizer>>, json: String) -> anyhow::Result<Searcher> {
+    pub fn new(
+        lemmatizer: Arc<Mutex<Lemmatizer>>,
+        json: String,
+        synonyms: Option<HashMap<String, Vec<String>>>,
+    ) -> anyhow::Result<Searcher> {
         let mut thread_dsu = ThreadDSU::new();
         let messages = deserialize_messages(json)?;
 
@@ -75,24 +167,242 @@ impl Searcher {
                 }
             }
         }
+        let mut vocabulary: Vec<&String> = thread_index.keys().collect();
+        vocabulary.sort_unstable();
+        let word_fst = Set::from_iter(vocabulary).expect("thread_index keys are sorted and unique");
+
+        let mut message_tokens = HashMap::new();
+        let mut message_phrase_tokens = HashMap::new();
+        for message in &messages {
+            let mut tokens = Vec::new();
+            let mut phrase_tokens = Vec::new();
+            for text_entity in &message.text_entities {
+                if let TextEntity::Lemmatizable(text) = text_entity {
+                    let words: Vec<&str> =
+                        text.to_lowercase().split(|c: char| !c.is_alphanumeric()).collect();
+                    tokens.extend(
+                        words
+                            .iter()
+                            .filter(|word| word.len() > 3)
+                            .map(|word| lemmatizer.lock().unwrap().lemmatize(word)),
+                    );
+                    phrase_tokens.extend(
+                        words
+                            .iter()
+                            .filter(|word| !word.is_empty())
+                            .map(|word| lemmatizer.lock().unwrap().lemmatize(word)),
+                    );
+                }
+            }
+            message_tokens.insert(message.id, tokens);
+            message_phrase_tokens.insert(message.id, phrase_tokens);
+        }
+
+        let mut thread_term_freq = Vec::with_capacity(threads.len());
+        let mut thread_token_count = Vec::with_capacity(threads.len());
+        for message_ids in &threads {
+            let mut freq: HashMap<String, usize> = HashMap::new();
+            let mut token_count = 0;
+            for message_id in message_ids {
+                if let Some(tokens) = message_tokens.get(message_id) {
+                    token_count += tokens.len();
+                    for token in tokens {
+                        *freq.entry(token.clone()).or_insert(0) += 1;
+                    }
+                }
+            }
+            thread_term_freq.push(freq);
+            thread_token_count.push(token_count);
+        }
+        let avgdl = if threads.is_empty() {
+            0.0
+        } else {
+            thread_token_count.iter().sum::<usize>() as f64 / threads.len() as f64
+        };
+
         Ok(Self {
             messages,
             threads,
             lemmatizer,
             thread_index,
+            word_fst,
+            message_tokens,
+            message_phrase_tokens,
+            thread_term_freq,
+            thread_token_count,
+            avgdl,
+            synonyms: synonyms.unwrap_or_default(),
+            thread_embeddings: None,
         })
     }
 
+    /// Texts to embed externally, one per thread, in thread-id order.
+    pub fn thread_texts(&self) -> Vec<String> {
+        self.threads
+            .iter()
+            .map(|message_ids| {
+                message_ids
+                    .iter()
+                    .flat_map(|message_id| &self.messages[*message_id].text_entities)
+                    .filter_map(|text_entity| match text_entity {
+                        TextEntity::Lemmatizable(text) => Some(text.as_str()),
+                        TextEntity::Illemmatizable(_) => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    pub fn set_thread_embeddings(&mut self, embeddings: Vec<Vec<f32>>) -> anyhow::Result<()> {
+        if embeddings.len() != self.threads.len() {
+            anyhow::bail!(
+                "expected {} thread embeddings, got {}",
+                self.threads.len(),
+                embeddings.len()
+            );
+        }
+        // All embeddings must share one dimension, or cosine_similarity would
+        // silently compare truncated vectors against the query embedding.
+        if let Some(dim) = embeddings.first().map(Vec::len) {
+            if let Some(mismatched) = embeddings.iter().find(|embedding| embedding.len() != dim) {
+                anyhow::bail!(
+                    "thread embeddings must all have the same dimension: expected {}, got {}",
+                    dim,
+                    mismatched.len()
+                );
+            }
+        }
+        self.thread_embeddings = Some(embeddings);
+        Ok(())
+    }
+
     fn find_threads_by_word(&self, word: String) -> Vec<usize> {
         utils::log!("find_threads_by_word({})", word);
-        let word = word.to_lowercase();
-        let word = self.lemmatizer.lock().unwrap().lemmatize(&word);
-        self.thread_index.get(&word).cloned().unwrap_or_default()
+        let mut result: Vec<usize> = Vec::new();
+        for term in self.matched_vocabulary_terms_for_word(&word) {
+            if let Some(ids) = self.thread_index.get(&term) {
+                result = MergeOr::new(result.iter(), ids.iter()).copied().collect();
+            }
+        }
+        result
+    }
+
+    /// Vocabulary terms a `Word(word)` leaf actually resolves to: the exact
+    /// lemma when indexed, otherwise whatever the typo-tolerant DFA matches.
+    /// Shared with BM25 scoring so relevance is computed against the terms a
+    /// thread was actually found through, not the raw (possibly misspelled)
+    /// query word.
+    fn matched_vocabulary_terms_for_word(&self, word: &str) -> Vec<String> {
+        let word = self.lemmatizer.lock().unwrap().lemmatize(&word.to_lowercase());
+
+        if self.thread_index.contains_key(&word) {
+            // Prefer the exact match when it exists, so a correctly spelled
+            // query never gets diluted by nearby typo matches.
+            return vec![word];
+        }
+
+        let budget = typo_budget(&word);
+        if budget == 0 {
+            return Vec::new();
+        }
+
+        let dfa = LevenshteinAutomatonBuilder::new(budget, true).build_dfa(&word);
+        self.stream_matching_terms(&dfa)
+    }
+
+    /// Streams the vocabulary FST against a Levenshtein DFA, returning the
+    /// matched terms (capped at `MAX_TYPO_EXPANSION`).
+    fn stream_matching_terms(&self, dfa: &DFA) -> Vec<String> {
+        let mut stream = self.word_fst.search(dfa).into_stream();
+        let mut matched_terms = Vec::new();
+        while let Some(term) = stream.next() {
+            if matched_terms.len() >= MAX_TYPO_EXPANSION {
+                break;
+            }
+            matched_terms.push(String::from_utf8(term.to_vec()).expect("fst keys are valid utf8"));
+        }
+        matched_terms
+    }
+
+    /// Streams the vocabulary FST for every key starting with `prefix`. As-you-type
+    /// search needs every match, not just a lexicographically-first sample
+    /// (unlike the typo-tolerant path, `MAX_TYPO_EXPANSION` does not apply
+    /// here: a common prefix dropping a real word like "programming" would be
+    /// a correctness bug, not a generosity knob). `find_threads_by_prefix`
+    /// merges the matched terms' posting lists in one pass, so this being
+    /// uncapped doesn't reintroduce the quadratic re-merge fixed earlier.
+    fn stream_prefix_terms(&self, prefix: &str) -> Vec<String> {
+        let mut stream = self
+            .word_fst
+            .search(fst::automaton::Str::new(prefix).starts_with())
+            .into_stream();
+        let mut matched_terms = Vec::new();
+        while let Some(term) = stream.next() {
+            matched_terms.push(String::from_utf8(term.to_vec()).expect("fst keys are valid utf8"));
+        }
+        matched_terms
+    }
+
+    fn find_threads_by_prefix(&self, prefix: String) -> Vec<usize> {
+        utils::log!("find_threads_by_prefix({})", prefix);
+        let prefix = prefix.to_lowercase();
+        let prefix = self.lemmatizer.lock().unwrap().lemmatize(&prefix);
+
+        // Collect every matched key's posting list up front and merge once,
+        // instead of re-merging the growing result against each key in turn.
+        let mut result: Vec<usize> = self
+            .stream_prefix_terms(&prefix)
+            .iter()
+            .flat_map(|term| self.thread_index.get(term).cloned().unwrap_or_default())
+            .collect();
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    fn find_threads_by_phrase(&self, words: Vec<String>) -> Vec<usize> {
+        utils::log!("find_threads_by_phrase({:?})", words);
+        let lemmas: Vec<String> = words
+            .iter()
+            .map(|word| self.lemmatizer.lock().unwrap().lemmatize(&word.to_lowercase()))
+            .collect();
+
+        // Words short enough to be excluded from `thread_index` (e.g. "new"
+        // in "new york") can't narrow the candidate set — AND-merging their
+        // empty posting list would wrongly drop every thread. Narrow using
+        // only the indexed words; the consecutive-run check below still
+        // requires every word, short ones included.
+        let mut candidate_threads: Option<Vec<usize>> = None;
+        for lemma in lemmas.iter().filter(|lemma| self.thread_index.contains_key(*lemma)) {
+            let ids = &self.thread_index[lemma];
+            candidate_threads = Some(match candidate_threads {
+                None => ids.clone(),
+                Some(existing) => MergeAnd::new(existing.iter(), ids.iter()).copied().collect(),
+            });
+        }
+        // A phrase made up entirely of short words can't be narrowed at all;
+        // fall back to checking every thread.
+        let candidate_threads =
+            candidate_threads.unwrap_or_else(|| (0..self.threads.len()).collect::<Vec<_>>());
+
+        candidate_threads
+            .into_iter()
+            .filter(|thread_id| {
+                self.threads[*thread_id].iter().any(|message_id| {
+                    self.message_phrase_tokens
+                        .get(message_id)
+                        .is_some_and(|tokens| contains_consecutive(tokens, &lemmas))
+                })
+            })
+            .collect()
     }
 
     fn find_threads_by_query(&self, query: SearchQuery) -> Vec<usize> {
         match query {
             SearchQuery::Word(word) => self.find_threads_by_word(word),
+            SearchQuery::Prefix(prefix) => self.find_threads_by_prefix(prefix),
+            SearchQuery::Phrase(words) => self.find_threads_by_phrase(words),
             SearchQuery::Or((query_left, query_right)) => MergeOr::new(
                 self.find_threads_by_query(*query_left).iter(),
                 self.find_threads_by_query(*query_right).iter(),
@@ -105,6 +415,12 @@ impl Searcher {
             )
             .copied()
             .collect(),
+            SearchQuery::Not((query_left, query_right)) => MergeNot::new(
+                self.find_threads_by_query(*query_left).iter(),
+                self.find_threads_by_query(*query_right).iter(),
+            )
+            .copied()
+            .collect(),
         }
     }
 
@@ -117,24 +433,242 @@ impl Searcher {
             .collect()
     }
 
-    pub fn find_threads(&self, query: String) -> anyhow::Result<Vec<ThreadSearchResult>> {
-        let query = Parser::new(Lexer::new(&query))?.parse()?;
+    /// Vocabulary terms a `Prefix(prefix)` leaf actually resolves to: every
+    /// key starting with the lemmatized prefix, uncapped, as returned by
+    /// `stream_prefix_terms`.
+    fn matched_vocabulary_terms_for_prefix(&self, prefix: &str) -> Vec<String> {
+        let prefix = self.lemmatizer.lock().unwrap().lemmatize(&prefix.to_lowercase());
+        self.stream_prefix_terms(&prefix)
+    }
+
+    /// Vocabulary terms contributing to relevance, gathered from every
+    /// matched leaf of the query tree (the excluded side of a `Not` is
+    /// skipped: it shouldn't make a thread that lacks it score higher).
+    ///
+    /// `Word`/`Prefix` leaves resolve to the terms they actually matched
+    /// (typo, prefix, synonym or split expansion can land on a vocabulary
+    /// term quite different from the raw query word), so BM25 scores a
+    /// thread against what found it rather than what the user typed.
+    fn scoring_terms(&self, query: &SearchQuery) -> Vec<String> {
+        match query {
+            SearchQuery::Word(word) => self.matched_vocabulary_terms_for_word(word),
+            SearchQuery::Prefix(prefix) => self.matched_vocabulary_terms_for_prefix(prefix),
+            SearchQuery::Phrase(words) => words
+                .iter()
+                .map(|word| self.lemmatizer.lock().unwrap().lemmatize(&word.to_lowercase()))
+                .collect(),
+            SearchQuery::And((left, right)) | SearchQuery::Or((left, right)) => {
+                let mut terms = self.scoring_terms(left);
+                terms.extend(self.scoring_terms(right));
+                terms
+            }
+            SearchQuery::Not((left, _)) => self.scoring_terms(left),
+        }
+    }
+
+    /// Okapi BM25 score of `thread_id` against `lemmas`.
+    fn bm25_score(&self, thread_id: usize, lemmas: &[String]) -> f64 {
+        let n = self.threads.len() as f64;
+        let dl = self.thread_token_count[thread_id] as f64;
+        let freq = &self.thread_term_freq[thread_id];
+        // `avgdl` is 0.0 when every thread has no indexable tokens; dl/avgdl
+        // would then be 0.0/0.0 = NaN. Treat the length ratio as 1.0 (every
+        // thread is "average length") instead of poisoning the score.
+        let length_ratio = if self.avgdl == 0.0 { 1.0 } else { dl / self.avgdl };
+        lemmas
+            .iter()
+            .filter_map(|lemma| {
+                let df = self.thread_index.get(lemma)?.len() as f64;
+                let tf = *freq.get(lemma)? as f64;
+                let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                Some(idf * (tf * (BM25_K1 + 1.0)) / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * length_ratio)))
+            })
+            .sum()
+    }
+
+    /// Rewrites `Word` leaves into an `Or` over the word itself, its known
+    /// synonyms, and (if the word is itself unindexed) a split into two
+    /// known vocabulary words. Adjacent words joined by `And` also gain a
+    /// concatenated-word alternative, recovering "chatsearcher" style typos.
+    fn expand_query(&self, query: SearchQuery) -> SearchQuery {
+        match query {
+            SearchQuery::Word(word) => self.expand_word(word),
+            SearchQuery::Prefix(word) => SearchQuery::Prefix(word),
+            SearchQuery::Phrase(words) => SearchQuery::Phrase(words),
+            SearchQuery::Or((left, right)) => SearchQuery::Or((
+                Box::new(self.expand_query(*left)),
+                Box::new(self.expand_query(*right)),
+            )),
+            SearchQuery::Not((left, right)) => SearchQuery::Not((
+                Box::new(self.expand_query(*left)),
+                Box::new(self.expand_query(*right)),
+            )),
+            SearchQuery::And((left, right)) => {
+                let concat = self.concat_word(&left, &right);
+                let expanded = SearchQuery::And((
+                    Box::new(self.expand_query(*left)),
+                    Box::new(self.expand_query(*right)),
+                ));
+                match concat {
+                    Some(concat) => SearchQuery::Or((Box::new(expanded), Box::new(concat))),
+                    None => expanded,
+                }
+            }
+        }
+    }
+
+    fn expand_word(&self, word: String) -> SearchQuery {
+        let lemma = self.lemmatizer.lock().unwrap().lemmatize(&word.to_lowercase());
+        let mut query = SearchQuery::Word(word);
+
+        if let Some(synonyms) = self.synonyms.get(&lemma) {
+            for synonym in synonyms {
+                query = SearchQuery::Or((
+                    Box::new(query),
+                    Box::new(SearchQuery::Word(synonym.clone())),
+                ));
+            }
+        }
+
+        if !self.thread_index.contains_key(&lemma) {
+            if let Some((first, second)) = self.split_word(&lemma) {
+                query = SearchQuery::Or((
+                    Box::new(query),
+                    Box::new(SearchQuery::And((
+                        Box::new(SearchQuery::Word(first)),
+                        Box::new(SearchQuery::Word(second)),
+                    ))),
+                ));
+            }
+        }
+
+        query
+    }
+
+    /// Splits `word` at the first point where both halves are already
+    /// vocabulary keys, e.g. "chatsearcher" -> ("chat", "searcher").
+    fn split_word(&self, word: &str) -> Option<(String, String)> {
+        // Split only at char boundaries (`char_indices`) rather than raw byte
+        // offsets: `word` may contain multi-byte characters, and splitting at
+        // an arbitrary byte offset would panic.
+        let char_count = word.chars().count();
+        word.char_indices()
+            .map(|(at, _)| at)
+            .skip(4)
+            .take(char_count.saturating_sub(7))
+            .find_map(|at| {
+                let (first, second) = word.split_at(at);
+                (self.thread_index.contains_key(first) && self.thread_index.contains_key(second))
+                    .then(|| (first.to_string(), second.to_string()))
+            })
+    }
+
+    /// If `left` and `right` are both plain words whose lemmas concatenate
+    /// into an existing vocabulary key, returns that key as a `Word` term.
+    fn concat_word(&self, left: &SearchQuery, right: &SearchQuery) -> Option<SearchQuery> {
+        let SearchQuery::Word(left) = left else {
+            return None;
+        };
+        let left = self.lemmatizer.lock().unwrap().lemmatize(&left.to_lowercase());
+
+        // The right-hand word of the *whole* query ends up as `Prefix`
+        // (chunk0-2's default trailing-prefix rule), so the common two-word
+        // case ("chat searcher") would otherwise never hit the concat branch.
+        // Check the concatenation as a prefix in that case rather than
+        // requiring it to already be a complete vocabulary key.
+        match right {
+            SearchQuery::Word(right) => {
+                let right = self.lemmatizer.lock().unwrap().lemmatize(&right.to_lowercase());
+                let concatenated = format!("{left}{right}");
+                self.thread_index
+                    .contains_key(&concatenated)
+                    .then_some(SearchQuery::Word(concatenated))
+            }
+            SearchQuery::Prefix(right) => {
+                let right = self.lemmatizer.lock().unwrap().lemmatize(&right.to_lowercase());
+                let concatenated = format!("{left}{right}");
+                (!self.stream_prefix_terms(&concatenated).is_empty())
+                    .then_some(SearchQuery::Prefix(concatenated))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn find_threads(
+        &self,
+        query: String,
+        options: SearchOptions,
+    ) -> anyhow::Result<Vec<ThreadSearchResult>> {
+        let parsed = Parser::new(Lexer::new(&query))?.parse()?;
+        let parsed = self.expand_query(parsed);
+        let lemmas = self.scoring_terms(&parsed);
 
-        let mut result: Vec<ThreadSearchResult> = self
-            .find_threads_by_query(query)
+        let text_scores: HashMap<usize, f64> = self
+            .find_threads_by_query(parsed)
             .into_iter()
-            .map(|thread_id| {
+            .map(|thread_id| (thread_id, self.bm25_score(thread_id, &lemmas)))
+            .collect();
+        let max_text_score = text_scores.values().cloned().fold(0.0_f64, f64::max);
+
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for (thread_id, score) in &text_scores {
+            let normalized = if max_text_score > 0.0 {
+                score / max_text_score
+            } else {
+                0.0
+            };
+            if normalized as f32 >= options.min_score_text {
+                scores.insert(*thread_id, normalized);
+            }
+        }
+
+        // The vector path runs only once both a query embedding and indexed
+        // thread embeddings are available; otherwise search stays lexical-only.
+        if let (Some(query_embedding), Some(thread_embeddings)) =
+            (&options.query_embedding, &self.thread_embeddings)
+        {
+            if let Some(dim) = thread_embeddings.first().map(Vec::len) {
+                if query_embedding.len() != dim {
+                    anyhow::bail!(
+                        "query embedding has dimension {}, expected {}",
+                        query_embedding.len(),
+                        dim
+                    );
+                }
+            }
+            for (thread_id, embedding) in thread_embeddings.iter().enumerate() {
+                let similarity = cosine_similarity(query_embedding, embedding);
+                // Normalize cosine similarity (-1..1) to the same 0..1 scale
+                // `min_score_text` is checked against, so both thresholds
+                // mean the same thing regardless of sort mode.
+                let normalized = ((similarity + 1.0) / 2.0) as f64;
+                if normalized as f32 < options.min_score_vector {
+                    continue;
+                }
+                *scores.entry(thread_id).or_insert(0.0) += normalized;
+            }
+        }
+
+        let mut result: Vec<ThreadSearchResult> = scores
+            .into_iter()
+            .map(|(thread_id, score)| {
                 let message_id = self.threads[thread_id].first().copied().unwrap();
                 let message = &self.messages[message_id];
                 ThreadSearchResult {
                     thread_id: thread_id as u32,
-                    score: 0,
+                    score: (score * 1000.0).round() as u32,
                     title_text: message.clone().into(),
                     date_unixtime: message.date_unixtime,
                 }
             })
             .collect();
-        result.sort_by_key(|thread| -(thread.date_unixtime as i32));
+        match options.sort_mode {
+            SortMode::Relevance => result.sort_by(|a, b| b.score.cmp(&a.score)),
+            SortMode::Date => result.sort_by_key(|thread| -(thread.date_unixtime as i32)),
+        }
+        if let Some(top_k) = options.top_k {
+            result.truncate(top_k);
+        }
         Ok(result)
     }
 
@@ -149,12 +683,16 @@ impl Searcher {
         &self,
         message_id_min: usize,
         message_id_max: usize,
-        query_words: Vec<String>,
-    ) -> Vec<MessageResult> {
+        query: String,
+    ) -> anyhow::Result<Vec<MessageResult>> {
         if message_id_min > message_id_max {
-            return Vec::new();
+            return Ok(Vec::new());
         }
-        self.messages[message_id_min..=message_id_max]
+        let parsed = Parser::new(Lexer::new(&query))?.parse()?;
+        let parsed = self.expand_query(parsed);
+        let matching_words = self.matching_words(&parsed);
+
+        Ok(self.messages[message_id_min..=message_id_max]
             .iter()
             .map(|message| {
                 let reply_to_text = message
@@ -162,50 +700,209 @@ impl Searcher {
                     .map(|reply_to_id| self.messages[reply_to_id].clone().into());
                 MessageResult {
                     message_id: message.id,
-                    text: self.get_highlighted_text(message.text_entities.clone(), &query_words),
+                    text: self.get_highlighted_text(message.text_entities.clone(), &matching_words),
                     reply_to_text,
                 }
             })
-            .collect()
+            .collect())
+    }
+
+    /// Builds the predicates highlighting should test tokens against: the
+    /// same exact/prefix/typo-DFA rules `find_threads_by_query` used to
+    /// decide which threads matched. The excluded side of a `Not` is
+    /// skipped, same as in `scoring_terms`.
+    fn matching_words(&self, query: &SearchQuery) -> MatchingWords {
+        let mut terms = Vec::new();
+        self.collect_matching_terms(query, &mut terms);
+        MatchingWords { terms }
+    }
+
+    fn collect_matching_terms(&self, query: &SearchQuery, terms: &mut Vec<MatchingTerm>) {
+        match query {
+            SearchQuery::Word(word) => {
+                let lemma = self.lemmatizer.lock().unwrap().lemmatize(&word.to_lowercase());
+                // Mirror `matched_vocabulary_terms_for_word`: an indexed exact
+                // lemma is matched exactly, so highlighting must not also
+                // light up typo-neighbors the search path never matched.
+                let budget = typo_budget(&lemma);
+                let dfa = (budget > 0 && !self.thread_index.contains_key(&lemma))
+                    .then(|| LevenshteinAutomatonBuilder::new(budget, true).build_dfa(&lemma));
+                terms.push(MatchingTerm::Single(SingleMatcher {
+                    lemma,
+                    is_prefix: false,
+                    dfa,
+                }));
+            }
+            SearchQuery::Prefix(word) => {
+                let lemma = self.lemmatizer.lock().unwrap().lemmatize(&word.to_lowercase());
+                terms.push(MatchingTerm::Single(SingleMatcher {
+                    lemma,
+                    is_prefix: true,
+                    dfa: None,
+                }));
+            }
+            SearchQuery::Phrase(words) => {
+                let lemmas = words
+                    .iter()
+                    .map(|word| self.lemmatizer.lock().unwrap().lemmatize(&word.to_lowercase()))
+                    .collect();
+                terms.push(MatchingTerm::Phrase(lemmas));
+            }
+            SearchQuery::And((left, right)) | SearchQuery::Or((left, right)) => {
+                self.collect_matching_terms(left, terms);
+                self.collect_matching_terms(right, terms);
+            }
+            SearchQuery::Not((left, _)) => self.collect_matching_terms(left, terms),
+        }
     }
 
-    fn get_highlighted_text(&self, text: Vec<TextEntity>, query_words: &[String]) -> Vec<Text> {
+    fn get_highlighted_text(&self, text: Vec<TextEntity>, matching_words: &MatchingWords) -> Vec<Text> {
         text.into_iter()
             .flat_map(|text_entity| match text_entity {
-                TextEntity::Lemmatizable(text) => self.highlight_substrings(text, query_words),
+                TextEntity::Lemmatizable(text) => self.highlight_substrings(text, matching_words),
                 TextEntity::Illemmatizable(text) => vec![Text::Plain(text)],
             })
             .collect()
     }
-    fn highlight_substrings(&self, target: String, queries: &[String]) -> Vec<Text> {
-        let mut result = Vec::new();
-        let mut target = target;
-        while !target.is_empty() {
-            let next_non_alphanumeric = target.find(|c: char| !c.is_alphanumeric());
-            let (word, rest) = match next_non_alphanumeric {
-                Some(index) => {
-                    let (word, rest) = target.split_at(index);
-                    (word.to_string(), rest.to_string())
+
+    fn highlight_substrings(&self, target: String, matching_words: &MatchingWords) -> Vec<Text> {
+        let segments = Self::tokenize(&target);
+
+        // Only segments long enough to have been indexed (same `len() > 3`
+        // filter as `thread_index`) are candidates for a match.
+        let candidates: Vec<(usize, String)> = segments
+            .iter()
+            .enumerate()
+            .filter_map(|(index, segment)| match segment {
+                Segment::Word(word) if word.len() > 3 => Some((
+                    index,
+                    self.lemmatizer.lock().unwrap().lemmatize(&word.to_lowercase()),
+                )),
+                _ => None,
+            })
+            .collect();
+        let candidate_lemmas: Vec<&str> = candidates.iter().map(|(_, lemma)| lemma.as_str()).collect();
+
+        let matched_segments: HashSet<usize> = matching_words
+            .matching_candidate_indices(&candidate_lemmas)
+            .into_iter()
+            .map(|candidate_index| candidates[candidate_index].0)
+            .collect();
+
+        segments
+            .into_iter()
+            .enumerate()
+            .map(|(index, segment)| match segment {
+                Segment::Word(word) if matched_segments.contains(&index) => Text::Highlight(word),
+                Segment::Word(word) | Segment::Other(word) => Text::Plain(word),
+            })
+            .collect()
+    }
+
+    /// Splits `target` into alphanumeric-word and separator segments,
+    /// preserving their order so highlighting can reconstruct the text.
+    fn tokenize(target: &str) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let mut rest = target;
+        while !rest.is_empty() {
+            let word_end = rest
+                .find(|c: char| !c.is_alphanumeric())
+                .unwrap_or(rest.len());
+            if word_end > 0 {
+                segments.push(Segment::Word(rest[..word_end].to_string()));
+                rest = &rest[word_end..];
+                continue;
+            }
+            let other_end = rest
+                .find(|c: char| c.is_alphanumeric())
+                .unwrap_or(rest.len());
+            segments.push(Segment::Other(rest[..other_end].to_string()));
+            rest = &rest[other_end..];
+        }
+        segments
+    }
+}
+
+enum Segment {
+    Word(String),
+    Other(String),
+}
+
+struct SingleMatcher {
+    lemma: String,
+    is_prefix: bool,
+    dfa: Option<DFA>,
+}
+
+impl SingleMatcher {
+    fn matches(&self, candidate: &str) -> bool {
+        if self.is_prefix {
+            return candidate.starts_with(&self.lemma);
+        }
+        if candidate == self.lemma {
+            return true;
+        }
+        self.dfa
+            .as_ref()
+            .is_some_and(|dfa| matches!(dfa.eval(candidate.as_bytes()), levenshtein_automata::Distance::Exact(_)))
+    }
+}
+
+enum MatchingTerm {
+    Single(SingleMatcher),
+    Phrase(Vec<String>),
+}
+
+impl MatchingTerm {
+    /// Length of the match starting at `candidates[at]`, or 0 if none.
+    fn match_len(&self, candidates: &[&str], at: usize) -> usize {
+        match self {
+            MatchingTerm::Single(matcher) => usize::from(matcher.matches(candidates[at])),
+            MatchingTerm::Phrase(lemmas) => {
+                let end = at + lemmas.len();
+                let matches = end <= candidates.len()
+                    && candidates[at..end]
+                        .iter()
+                        .eq(lemmas.iter().map(String::as_str));
+                if matches {
+                    lemmas.len()
+                } else {
+                    0
                 }
-                None => (target.clone(), "".into()),
-            };
-            target = rest;
-            let lemmatized_word = self
-                .lemmatizer
-                .lock()
-                .unwrap()
-                .lemmatize(&word.to_lowercase());
-            if queries.contains(&lemmatized_word) {
-                result.push(Text::Highlight(word));
-            } else {
-                result.push(Text::Plain(word));
             }
-            while !target.is_empty() && !target.chars().peekable().peek().unwrap().is_alphanumeric()
-            {
-                result.push(Text::Plain(target.chars().next().unwrap().to_string()));
-                target = target.chars().skip(1).collect();
+        }
+    }
+}
+
+/// Per-query-term matching predicates, shared between the search path and
+/// highlighting so what the user sees highlighted matches why a thread was
+/// found: exact lemma, prefix, or edit-distance DFA, plus whole phrase runs.
+struct MatchingWords {
+    terms: Vec<MatchingTerm>,
+}
+
+impl MatchingWords {
+    /// Indices into `candidates` that should be highlighted. At each
+    /// position, the longest matching term wins (important once phrase
+    /// terms can match a multi-word run overlapping a single-word term),
+    /// and matches don't overlap.
+    fn matching_candidate_indices(&self, candidates: &[&str]) -> HashSet<usize> {
+        let mut matched = HashSet::new();
+        let mut index = 0;
+        while index < candidates.len() {
+            let longest = self
+                .terms
+                .iter()
+                .map(|term| term.match_len(candidates, index))
+                .max()
+                .unwrap_or(0);
+            if longest == 0 {
+                index += 1;
+                continue;
             }
+            matched.extend(index..index + longest);
+            index += longest;
         }
-        result
+        matched
     }
 }